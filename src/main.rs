@@ -1,6 +1,5 @@
 use error::MainError;
 use std::io::Read;
-use std::str;
 use tudelft_dsmr_output_generator::Graphs;
 use tudelft_dsmr_output_generator::{
     current_over_time::{CurrentData, CurrentOverTime},
@@ -10,6 +9,11 @@ use tudelft_dsmr_output_generator::{
 
 /// Contains `MainError`, and code to convert `PlotError` and `io::Error` into a `MainError`
 mod error;
+/// Contains `ParserState`, the line-by-line telegram accumulator shared by
+/// the batch parser and `TelegramReader`, the incremental streaming reader.
+mod reader;
+
+pub use reader::TelegramReader;
 
 fn get_month_as_uint(date: &str) -> u8 {
     match &date[..date.len() - 1] {
@@ -42,7 +46,7 @@ fn get_month_as_uint(date: &str) -> u8 {
 type DsmrV10 = Vec<TelegramV10>;
 
 #[derive(Debug)]
-struct TelegramV10 {
+pub struct TelegramV10 {
     date: i64,
     event_log: EventLog,
     information: Electricity,
@@ -80,6 +84,7 @@ pub enum ParseError {
     DuplicateFieldId,
     MissingElectricity,
     ChildTelegramNotSupported,
+    Io(std::io::Error),
 }
 
 /// Parse v10 of DSMR spec
@@ -88,236 +93,20 @@ fn parse_v10(input: &str) -> Result<DsmrV10, ParseError> {
     lines.next();
 
     let mut dsmr = DsmrV10::new();
-    let mut electricity = Electricity {
-        power: vec![0.0, 0.0, 0.0],
-        voltage: vec![0.0, 0.0, 0.0],
-        current: vec![0.0, 0.0, 0.0],
-
-        total_consumed: 0.0,
-        total_produced: 0.0,
-    };
-
-    // TODO: i64 should be UnixTimeStamp for clarity
-    let mut telegram_date = 0i64;
-    let mut dates = Vec::new();
-    let mut messages = Vec::new();
-    let mut severities = Vec::new();
-
-    let mut seen_info_type = false;
-    let mut has_electricity = false;
-    let mut has_telegram_date = false;
+    let mut state = reader::ParserState::new();
+
     for line in lines {
         if line.is_empty() {
             continue;
         }
 
         let bytes = line.as_bytes();
-        match bytes[0] {
-            b'1' => {
-                // parse telegram header
-
-                if bytes[2] == b'1' && bytes[4] != b'0' {
-                    return Err(ParseError::ChildTelegramNotSupported);
-                }
-                if bytes[2] == b'2' {
-                    // telegram end
-                    if !has_electricity {
-                        return Err(ParseError::MissingElectricity);
-                    }
-                    if !has_telegram_date {
-                        return Err(ParseError::NoDate);
-                    }
-
-                    // sort by the event id
-                    dates.sort_unstable_by(|ent_a: &(u32, i64), ent_b: &(u32, i64)| {
-                        ent_a.0.partial_cmp(&ent_b.0).unwrap()
-                    });
-                    severities.sort_unstable_by(
-                        |ent_a: &(u32, Severity), ent_b: &(u32, Severity)| {
-                            ent_a.0.partial_cmp(&ent_b.0).unwrap()
-                        },
-                    );
-                    messages.sort_unstable_by(|ent_a: &(u32, String), ent_b: &(u32, String)| {
-                        ent_a.0.partial_cmp(&ent_b.0).unwrap()
-                    });
-
-                    // ensure the whole thing correlate
-                    assert!(dates.len() == severities.len());
-                    assert!(dates.len() == messages.len());
-
-                    let mut event_log = EventLog {
-                        ids: Vec::new(),
-                        dates: Vec::new(),
-                        messages: Vec::new(),
-                        severities: Vec::new(),
-                    };
-                    // avoiding iterators here because it won't be easy to follow
-                    for i in 0..dates.len() {
-                        let ent_date = dates[i];
-                        let ent_sev = severities[i];
-                        let ent_msg = messages[i].clone();
-
-                        // ensure the IDs are the same
-                        assert!(ent_date.0 == ent_msg.0);
-                        assert!(ent_date.0 == ent_sev.0);
-
-                        let id = ent_date.0;
-
-                        event_log.ids.push(id);
-                        event_log.dates.push(ent_date.1);
-                        event_log.messages.push(ent_msg.1);
-                        event_log.severities.push(ent_sev.1);
-                    }
-
-                    // push it to list
-                    let telegram_v10 = TelegramV10 {
-                        date: telegram_date.clone(),
-                        event_log: event_log.clone(),
-                        information: electricity.clone(),
-                    };
-                    // get the compiler to shut up
-                    _ = telegram_v10.date;
-                    _ = telegram_v10.event_log;
-                    _ = telegram_v10.information;
-                    dsmr.push(telegram_v10);
-                }
-
-                // new telegram
-                seen_info_type = false;
-                has_electricity = false;
-                has_telegram_date = false;
-
-                telegram_date = 0;
-                dates.clear();
-                severities.clear();
-                messages.clear();
-
-                electricity.power = [0.0].repeat(3);
-                electricity.voltage = [0.0].repeat(3);
-                electricity.current = [0.0].repeat(3);
-                electricity.total_consumed = 0.0;
-                electricity.total_produced = 0.0;
-            }
-            b'2' => {
-                // parse this Telegram's date
-
-                let idx = line.rfind(')').unwrap();
-                let inner = &bytes[5..idx];
-                let date = String::from_utf8_lossy(inner).to_string();
-                let yy =
-                    2000 /* account for this century */ + (&date[0..2]).parse::<u16>().unwrap();
-                let dd: u8 = (&date[7..9]).parse().unwrap();
-                let hh: u8 = (&date[10..12]).parse().unwrap();
-                let mm: u8 = (&date[13..15]).parse().unwrap();
-                let ss: u8 = (&date[16..18]).parse().unwrap();
-                let mmm: u8 = get_month_as_uint(&date[3..6]);
-
-                let dts = inner[inner.len() - 2] as char;
-                telegram_date = tudelft_dsmr_output_generator::date_to_timestamp(
-                    yy,
-                    mmm,
-                    dd,
-                    hh,
-                    mm,
-                    ss,
-                    dts == 'S',
-                )
-                .unwrap();
-                has_telegram_date = true;
-            }
-            b'3' => {
-                // parse eventlog
-
-                // in 3.x.n; x is discriminant, n is event id
-                let discriminant = bytes[2] as char;
-                let paren = line.rfind(')').unwrap();
-                let val = &bytes[7..paren];
-
-                let event_id = bytes[4] as char;
-                let event_id = event_id.to_digit(10).unwrap();
-                match discriminant {
-                    '1' => severities.push((
-                        event_id,
-                        if matches!(bytes[7] as char, 'H') {
-                            Severity::High
-                        } else {
-                            Severity::Low
-                        },
-                    )),
-                    '2' => messages.push((event_id, String::from_utf8_lossy(&val).to_string())),
-                    '3' => {
-                        // parse date
-
-                        // <yy-mmm-dd hh:mm:ss>
-                        let date = String::from_utf8_lossy(&val[..val.len() - 4]).to_string();
-
-                        let dts = val[val.len() - 2] as char;
-                        let yy = 2000 /* account for this century */ + (&date[0..2]).parse::<u16>().unwrap();
-                        let dd: u8 = (&date[7..9]).parse().unwrap();
-                        let hh: u8 = (&date[10..12]).parse().unwrap();
-                        let mm: u8 = (&date[13..15]).parse().unwrap();
-                        let ss: u8 = (&date[16..18]).parse().unwrap();
-                        let mmm: u8 = get_month_as_uint(&date[3..6]);
-
-                        dates.push((
-                            event_id,
-                            tudelft_dsmr_output_generator::date_to_timestamp(
-                                yy,
-                                mmm,
-                                dd,
-                                hh,
-                                mm,
-                                ss,
-                                dts == 'S',
-                            )
-                            .unwrap(),
-                        ))
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            b'4' => {
-                // parse informtion type
-                if seen_info_type {
-                    return Err(ParseError::DuplicateFieldId);
-                }
-                seen_info_type = true;
-            }
-            b'7' => {
-                // parse electricity
-                if !seen_info_type {
-                    return Err(ParseError::MissingElectricity);
-                }
-                has_electricity = true;
-
-                let phase = (bytes[4] - b'1') as usize; // get 0-based index for use in phases vectors
-                assert!(phase <= 2);
-
-                let discriminant = bytes[2] as char;
-                let star = line.find('*').unwrap();
-                let val = str::from_utf8(&bytes[7..star]).unwrap();
-                let val_f64 = val.parse::<f64>().unwrap();
-
-                // just in case, and for simplicity
-                if discriminant == '0' || discriminant > '4' {
-                    unreachable!();
-                }
-                match discriminant {
-                    '1' => electricity.voltage[phase] = val_f64,
-                    '2' => electricity.current[phase] = val_f64,
-                    '3' => electricity.power[phase] = val_f64,
-                    '4' => {
-                        if bytes[4] == b'1' {
-                            electricity.total_consumed = val_f64;
-                        } else {
-                            electricity.total_produced = val_f64;
-                        }
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            _ => {}
+        if bytes[0] == b'1' && bytes[2] == b'2' {
+            // telegram end
+            dsmr.push(state.finish_telegram()?);
         }
+
+        state.feed_line(line)?;
     }
     Ok(dsmr)
 }